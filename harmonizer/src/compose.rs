@@ -2,9 +2,11 @@
 # Compose graphql schemas
 */
 
+use crate::error::HarmonizerError;
 use deno_core::{op_sync, JsRuntime};
 use serde::{Deserialize, Serialize};
-use std::sync::mpsc::channel;
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::{fmt::Display, io::Write};
 use thiserror::Error;
 
@@ -114,20 +116,92 @@ impl CompositionError {
     }
 }
 
-/// The `harmonize` function receives a [`ServiceList`] and invokes JavaScript
-/// composition on it.
+/// A reusable, stateful composer that keeps a composed supergraph warm.
+///
+/// Constructing a [`Composer`] pays the cost of starting a [`JsRuntime`] and
+/// loading `runtime.js` and the bundled `bridge.js` exactly once. A gateway
+/// that recomposes its supergraph whenever a subgraph changes can reuse the
+/// same [`Composer`] across calls to [`Composer::compose`] instead of paying
+/// that initialization cost every time.
 ///
-pub fn compose(service_list: ServiceList) -> Result<String, Vec<CompositionError>> {
-    // Initialize a runtime instance
-    let mut runtime = JsRuntime::new(Default::default());
+/// Because a [`JsRuntime`] is neither [`Send`] nor [`Sync`], a [`Composer`]
+/// is not either, and must be owned and driven from a single thread. A
+/// gateway that wants to compose from multiple threads should instead own a
+/// worker thread that holds the [`Composer`] and communicates with callers
+/// over its own channel, handing back each composition result as it's
+/// reported into [`Composer`]'s internal result buffer.
+pub struct Composer {
+    runtime: JsRuntime,
+    result_buffer: Rc<RefCell<Option<serde_json::Value>>>,
+}
 
-    // We'll use this channel to get the results
-    let (tx, rx) = channel();
+impl Composer {
+    /// Create a [`Composer`], initializing the JS runtime once.
+    ///
+    /// There's deliberately no `impl Default for Composer`: initializing the
+    /// JS runtime is fallible (a corrupt bundled `bridge.js` surfaces as a
+    /// [`HarmonizerError`]), and `Default::default()` has no way to report
+    /// that, so callers must go through [`Composer::new`] instead.
+    pub fn new() -> Result<Composer, HarmonizerError> {
+        let mut runtime = JsRuntime::new(Default::default());
+        let result_buffer = Rc::new(RefCell::new(None));
 
-    // The first thing we do is define an op so we can print data to STDOUT,
-    // because by default the JavaScript console functions are just stubs (they
-    // don't do anything).
+        register_print_op(&mut runtime);
+        register_result_op(&mut runtime, Rc::clone(&result_buffer));
+
+        // The runtime automatically contains a Deno.core object with several
+        // functions for interacting with it.
+        runtime
+            .execute("<init>", include_str!("../js/runtime.js"))
+            .map_err(|e| HarmonizerError::BridgeInitialization(e.to_string()))?;
+
+        // Load the composition library.
+        runtime
+            .execute("bridge.js", include_str!("../dist/bridge.js"))
+            .map_err(|e| HarmonizerError::BridgeInitialization(e.to_string()))?;
+
+        Ok(Composer {
+            runtime,
+            result_buffer,
+        })
+    }
 
+    /// Compose `service_list` into a supergraph, reusing the already-warm
+    /// JS runtime.
+    pub fn compose(&mut self, service_list: ServiceList) -> Result<String, HarmonizerError> {
+        // We literally just turn it into a JSON object that we'll execute
+        // within the runtime.
+        let service_list_javascript = format!(
+            "serviceList = {}",
+            serde_json::to_string(&service_list)
+                .map_err(|e| HarmonizerError::RuntimeExecution(e.to_string()))?
+        );
+
+        self.runtime
+            .execute("<set_service_list>", &service_list_javascript)
+            .map_err(|e| HarmonizerError::RuntimeExecution(e.to_string()))?;
+
+        self.runtime
+            .execute("do_compose.js", include_str!("../js/do_compose.js"))
+            .map_err(|e| HarmonizerError::RuntimeExecution(e.to_string()))?;
+
+        let value = self.result_buffer.borrow_mut().take().ok_or_else(|| {
+            HarmonizerError::ResultDeserialization(
+                "do_compose.js did not report a result".to_string(),
+            )
+        })?;
+
+        let result: Result<String, Vec<CompositionError>> = serde_json::from_value(value)
+            .map_err(|e| HarmonizerError::ResultDeserialization(e.to_string()))?;
+
+        Ok(result?)
+    }
+}
+
+/// Register the op that lets the JS bridge print to STDOUT, because by
+/// default the JavaScript console functions are just stubs (they don't do
+/// anything).
+fn register_print_op(runtime: &mut JsRuntime) {
     // Register the op for outputting bytes to stdout. It can be invoked with
     // Deno.core.dispatch and the id this method returns or
     // Deno.core.dispatchByName and the name provided.
@@ -148,50 +222,35 @@ pub fn compose(service_list: ServiceList) -> Result<String, Vec<CompositionError
             Ok(()) // No meaningful result
         }),
     );
+}
 
+/// Register the op that the JS bridge uses to report the composition result
+/// back into `result_buffer`, so it can be read once `execute` returns.
+fn register_result_op(
+    runtime: &mut JsRuntime,
+    result_buffer: Rc<RefCell<Option<serde_json::Value>>>,
+) {
     runtime.register_op(
         "op_composition_result",
         op_sync(move |_state, value, _zero_copy| {
-            tx.send(serde_json::from_value(value).expect("deserializing composition result"))
-                .expect("channel must be open");
+            *result_buffer.borrow_mut() = Some(value);
 
             Ok(serde_json::json!(null))
 
             // Don't return anything to JS
         }),
     );
+}
 
-    // The runtime automatically contains a Deno.core object with several
-    // functions for interacting with it.
-    runtime
-        .execute(
-            "<init>",
-            include_str!("../js/runtime.js"),
-        )
-        .expect("unable to initialize bridge runtime environment");
-
-    // Load the composition library.
-    runtime
-        .execute("bridge.js", include_str!("../dist/bridge.js"))
-        .expect("unable to evaluate composition module");
-
-    // We literally just turn it into a JSON object that we'll execute within
-    // the runtime.
-    let service_list_javascript = format!(
-        "serviceList = {}",
-        serde_json::to_string(&service_list)
-            .expect("unable to serialize service list into JavaScript runtime")
-    );
-
-    runtime
-        .execute("<set_service_list>", &service_list_javascript)
-        .expect("unable to evaluate service list in JavaScript runtime");
-
-    runtime
-        .execute("do_compose.js", include_str!("../js/do_compose.js"))
-        .expect("unable to invoke do_compose in JavaScript runtime");
-
-    rx.recv().expect("channel remains open")
+/// The `harmonize` function receives a [`ServiceList`] and invokes JavaScript
+/// composition on it.
+///
+/// This is a convenience wrapper around [`Composer`] for callers that only
+/// need to compose a supergraph once. Recomposing repeatedly should instead
+/// construct one [`Composer`] and call [`Composer::compose`] repeatedly,
+/// which avoids re-initializing the JS runtime on every call.
+pub fn compose(service_list: ServiceList) -> Result<String, HarmonizerError> {
+    Composer::new()?.compose(service_list)
 }
 
 #[cfg(test)]
@@ -236,4 +295,64 @@ mod tests {
         ])
         .unwrap());
     }
+
+    #[test]
+    fn composer_can_be_reused_across_calls() {
+        use crate::compose::{Composer, ServiceDefinition};
+
+        let mut composer = Composer::new().unwrap();
+
+        let first = composer
+            .compose(vec![ServiceDefinition::new(
+                "users",
+                "undefined",
+                "
+            type User {
+              id: ID
+            }
+
+            type Query {
+              users: [User!]
+            }
+          ",
+            )])
+            .unwrap();
+
+        let second = composer
+            .compose(vec![ServiceDefinition::new(
+                "movies",
+                "undefined",
+                "
+            type Movie {
+              title: String
+            }
+
+            type Query {
+              movies: [Movie!]
+            }
+          ",
+            )])
+            .unwrap();
+
+        assert_ne!(first, second);
+
+        // Composing the original service list again should be unaffected by
+        // the intervening call, proving no state leaked between calls.
+        let third = composer
+            .compose(vec![ServiceDefinition::new(
+                "users",
+                "undefined",
+                "
+            type User {
+              id: ID
+            }
+
+            type Query {
+              users: [User!]
+            }
+          ",
+            )])
+            .unwrap();
+        assert_eq!(first, third);
+    }
 }
@@ -0,0 +1,69 @@
+/*!
+# Recoverable errors from the JS bridge
+*/
+
+use crate::compose::CompositionError;
+use crate::introspect::IntrospectionError;
+use crate::plan::PlanningError;
+use thiserror::Error;
+
+/// An error that can occur while driving the embedded JS bridge.
+///
+/// This separates infrastructure failures -- a corrupt bundled `bridge.js`,
+/// an OOM in V8, a malformed result -- from the recoverable, user-facing
+/// domain errors already reported by composition, planning, and
+/// introspection. Infrastructure failures used to `panic!` via `.expect()`;
+/// returning them here instead lets a long-running server survive a single
+/// bad schema or query, following the pattern of separating recoverable
+/// user errors from panics seen in the router's layered error enums.
+#[derive(Debug, Error, PartialEq)]
+pub enum HarmonizerError {
+    /// The JS bridge failed to initialize: the `JsRuntime` couldn't be
+    /// created, or `runtime.js`/`bridge.js` failed to evaluate.
+    #[error("failed to initialize the JS bridge: {0}")]
+    BridgeInitialization(String),
+
+    /// A step already pushed into an initialized runtime failed to execute.
+    #[error("failed to execute a step in the JS bridge: {0}")]
+    RuntimeExecution(String),
+
+    /// The result the bridge reported couldn't be deserialized, or no
+    /// result was reported at all.
+    #[error("failed to deserialize a result from the JS bridge: {0}")]
+    ResultDeserialization(String),
+
+    /// Composition failed for a recoverable, user-facing reason.
+    #[error("composition failed")]
+    Composition(Vec<CompositionError>),
+
+    /// Planning failed for a recoverable, user-facing reason.
+    #[error("planning failed")]
+    Planning(Vec<PlanningError>),
+
+    /// Introspection failed for a recoverable, user-facing reason.
+    #[error("introspection failed")]
+    Introspection(Vec<IntrospectionError>),
+}
+
+// `#[from]` isn't used for these three variants because thiserror also wires
+// it up as `Error::source()`, which requires the field type to implement
+// `std::error::Error` -- and `Vec<T>` never does, even when `T` does. These
+// manual impls give `?` the same conversion without that requirement.
+
+impl From<Vec<CompositionError>> for HarmonizerError {
+    fn from(errors: Vec<CompositionError>) -> Self {
+        HarmonizerError::Composition(errors)
+    }
+}
+
+impl From<Vec<PlanningError>> for HarmonizerError {
+    fn from(errors: Vec<PlanningError>) -> Self {
+        HarmonizerError::Planning(errors)
+    }
+}
+
+impl From<Vec<IntrospectionError>> for HarmonizerError {
+    fn from(errors: Vec<IntrospectionError>) -> Self {
+        HarmonizerError::Introspection(errors)
+    }
+}
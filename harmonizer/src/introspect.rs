@@ -0,0 +1,191 @@
+/*!
+# Answer introspection queries
+*/
+
+use crate::error::HarmonizerError;
+use deno_core::{op_sync, JsRuntime};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::{fmt::Display, io::Write};
+use thiserror::Error;
+
+/// The standard `__schema` introspection query, used when [`introspect`] is
+/// called without a caller-supplied query.
+pub const DEFAULT_INTROSPECTION_QUERY: &str = include_str!("../js/introspection_query.graphql");
+
+/// An error which occurred during JavaScript introspection.
+///
+/// The shape of this error is meant to mimick that of the error created within
+/// JavaScript, which is a [`GraphQLError`] from the [`graphql-js`] library.
+///
+/// [`graphql-js']: https://npm.im/graphql
+/// [`GraphQLError`]: https://github.com/graphql/graphql-js/blob/3869211/src/error/GraphQLError.js#L18-L75
+#[derive(Debug, Error, Serialize, Deserialize, PartialEq)]
+pub struct IntrospectionError {
+    /// A human-readable description of the error that prevented introspection.
+    pub message: Option<String>,
+    /// [`IntrospectionErrorExtensions`]
+    pub extensions: Option<IntrospectionErrorExtensions>,
+}
+
+impl Display for IntrospectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(msg) = &self.message {
+            f.write_fmt(format_args!("{code}: {msg}", code = self.code(), msg = msg))
+        } else {
+            f.write_str(self.code())
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+/// Errors
+pub struct IntrospectionErrorExtensions {
+    /// The error code
+    pub code: String,
+}
+
+/// An error that was received during introspection within JavaScript.
+impl IntrospectionError {
+    /// Retrieve the error code from an error received during introspection.
+    pub fn code(&self) -> &str {
+        match self.extensions {
+            Some(ref ext) => &*ext.code,
+            None => "UNKNOWN",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct IntrospectionContext {
+    schema: String,
+    query: String,
+}
+
+/// Answer an introspection `query` against `schema` by calling in to JS.
+///
+/// When `query` is empty, [`DEFAULT_INTROSPECTION_QUERY`] — a standard
+/// `__schema` introspection query — is run instead, which is enough to back
+/// a cached `__schema` response without standing up a separate GraphQL
+/// server.
+pub fn introspect(schema: String, query: String) -> Result<String, HarmonizerError> {
+    // Initialize a runtime instance
+    let mut runtime = JsRuntime::new(Default::default());
+    let result_buffer = Rc::new(RefCell::new(None));
+
+    register_print_op(&mut runtime);
+    register_result_op(&mut runtime, Rc::clone(&result_buffer));
+
+    // The runtime automatically contains a Deno.core object with several
+    // functions for interacting with it.
+    runtime
+        .execute("<init>", include_str!("../js/runtime.js"))
+        .map_err(|e| HarmonizerError::BridgeInitialization(e.to_string()))?;
+
+    // Load the composition library, which also embeds graphql-js and so is
+    // reused here for introspection.
+    runtime
+        .execute("bridge.js", include_str!("../dist/bridge.js"))
+        .map_err(|e| HarmonizerError::BridgeInitialization(e.to_string()))?;
+
+    let context = IntrospectionContext {
+        schema,
+        query: if query.is_empty() {
+            DEFAULT_INTROSPECTION_QUERY.to_string()
+        } else {
+            query
+        },
+    };
+
+    let context_javascript = format!(
+        "context = {}",
+        serde_json::to_string(&context)
+            .map_err(|e| HarmonizerError::RuntimeExecution(e.to_string()))?
+    );
+
+    runtime
+        .execute("<set_context>", &context_javascript)
+        .map_err(|e| HarmonizerError::RuntimeExecution(e.to_string()))?;
+
+    runtime
+        .execute("do_introspect.js", include_str!("../js/do_introspect.js"))
+        .map_err(|e| HarmonizerError::RuntimeExecution(e.to_string()))?;
+
+    let value = result_buffer.borrow_mut().take().ok_or_else(|| {
+        HarmonizerError::ResultDeserialization(
+            "do_introspect.js did not report a result".to_string(),
+        )
+    })?;
+
+    let result: Result<String, Vec<IntrospectionError>> = serde_json::from_value(value)
+        .map_err(|e| HarmonizerError::ResultDeserialization(e.to_string()))?;
+
+    Ok(result?)
+}
+
+/// Register the op that lets the JS bridge print to STDOUT, because by
+/// default the JavaScript console functions are just stubs (they don't do
+/// anything).
+fn register_print_op(runtime: &mut JsRuntime) {
+    // Register the op for outputting bytes to stdout. It can be invoked with
+    // Deno.core.dispatch and the id this method returns or
+    // Deno.core.dispatchByName and the name provided.
+    runtime.register_op(
+        "op_print",
+        // The op_fn callback takes a state object OpState,
+        // a structured arg of type `T` and an optional ZeroCopyBuf,
+        // a mutable reference to a JavaScript ArrayBuffer
+        op_sync(|_state, _msg: Option<String>, zero_copy| {
+            let mut out = std::io::stdout();
+
+            // Write the contents of every buffer to stdout
+            if let Some(buf) = zero_copy {
+                out.write_all(&buf)
+                    .expect("failure writing buffered output");
+            }
+
+            Ok(()) // No meaningful result
+        }),
+    );
+}
+
+/// Register the op that the JS bridge uses to report the introspection
+/// result back into `result_buffer`, so it can be read once `execute`
+/// returns.
+fn register_result_op(
+    runtime: &mut JsRuntime,
+    result_buffer: Rc<RefCell<Option<serde_json::Value>>>,
+) {
+    runtime.register_op(
+        "op_composition_result",
+        op_sync(move |_state, value, _zero_copy| {
+            *result_buffer.borrow_mut() = Some(value);
+
+            Ok(serde_json::json!(null))
+
+            // Don't return anything to JS
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const SCHEMA: &str = include_str!("testdata/schema.graphql");
+
+    #[test]
+    fn it_works() {
+        insta::assert_snapshot!(introspect(SCHEMA.to_string(), "".to_string()).unwrap());
+    }
+
+    #[test]
+    fn invalid_schema_is_caught() {
+        let result = Err(HarmonizerError::Introspection(vec![IntrospectionError {
+            message: Some("Syntax Error: Unexpected Name \"Garbage\".".to_string()),
+            extensions: None,
+        }]));
+        assert_eq!(result, introspect("Garbage".to_string(), "".to_string()));
+    }
+}
@@ -0,0 +1,16 @@
+/*!
+# harmonizer
+
+This crate embeds [`graphql-js`] through a JavaScript bridge to provide
+composition, planning, and introspection for Apollo Federation supergraphs
+from Rust.
+
+[`graphql-js`]: https://npm.im/graphql
+*/
+
+pub mod compose;
+pub mod error;
+pub mod introspect;
+pub mod plan;
+
+pub use error::HarmonizerError;
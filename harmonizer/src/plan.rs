@@ -2,9 +2,12 @@
 # Create a query plan
 */
 
+use crate::error::HarmonizerError;
 use deno_core::{op_sync, JsRuntime};
 use serde::{Deserialize, Serialize};
-use std::sync::mpsc::channel;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
 use std::{fmt::Display, io::Write};
 use thiserror::Error;
 
@@ -38,7 +41,15 @@ pub struct OperationalContext {
     pub schema: String,
     /// The query
     pub query: String,
-    /// The operation
+    /// The operation to plan, when `query` contains more than one named
+    /// operation.
+    ///
+    /// An empty string means "the single anonymous or only operation in
+    /// `query`", which is the common case. When `query` has multiple named
+    /// operations, `operation` must name exactly one of them; leaving it
+    /// empty, or naming an operation `query` doesn't define, are
+    /// disambiguation failures, and surface as a [`PlanningError`] coded
+    /// `OPERATION_RESOLUTION_FAILURE` rather than silently picking one.
     pub operation: String,
 }
 
@@ -87,19 +98,460 @@ impl PlanningError {
     }
 }
 
-/// Create the query plan by calling in to JS.
+/// A structured, traversable query plan.
+///
+/// This mirrors the JSON shape emitted by the federation query planner, so
+/// routers can walk the plan and execute its fetches without re-parsing or
+/// string-munging the JSON themselves. The raw JSON the plan was built from
+/// remains available via [`QueryPlan::raw`] for debugging.
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct QueryPlan {
+    /// The root of the plan, or `None` when the operation requires no
+    /// fetches (e.g. it only selects `__typename`).
+    pub node: Option<PlanNode>,
+    /// The raw JSON the bridge returned, retained for debugging.
+    #[serde(skip)]
+    raw: String,
+}
+
+impl QueryPlan {
+    /// The raw JSON this [`QueryPlan`] was parsed from, useful when
+    /// debugging a plan that doesn't look the way you'd expect.
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+}
+
+/// A single step of a [`QueryPlan`].
+///
+/// Tagged on the JSON `kind` field (`"Fetch"`, `"Sequence"`, etc.) to match
+/// the shape the federation query planner emits.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "kind")]
+pub enum PlanNode {
+    /// Run the contained nodes one after another, in order.
+    Sequence {
+        /// The nodes to run, in order.
+        nodes: Vec<PlanNode>,
+    },
+    /// Run the contained nodes concurrently.
+    Parallel {
+        /// The nodes to run concurrently.
+        nodes: Vec<PlanNode>,
+    },
+    /// Fetch data from a single subgraph.
+    #[serde(rename_all = "camelCase")]
+    Fetch {
+        /// The subgraph to fetch from.
+        service_name: String,
+        /// The entity representation fields this fetch requires from a
+        /// preceding fetch, if any.
+        requires: Option<Vec<Selection>>,
+        /// The names of the variables this fetch's `operation` references.
+        variable_usages: Vec<String>,
+        /// The GraphQL operation to send to the subgraph.
+        operation: String,
+    },
+    /// Re-root the contained node at `path` within the overall response.
+    Flatten {
+        /// The path, from the root of the response, to re-root `node` at.
+        path: Vec<String>,
+        /// The node to run once re-rooted.
+        node: Box<PlanNode>,
+    },
+}
+
+/// A single selected field or inline fragment within a [`PlanNode::Fetch`]'s
+/// `requires`.
+///
+/// Tagged on the JSON `kind` field, same as [`PlanNode`] -- the federation
+/// query planner emits variant tags in PascalCase (`"Field"`,
+/// `"InlineFragment"`), so there's no container-level `rename_all` here.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "kind")]
+pub enum Selection {
+    /// A selected field, and the sub-selections made on it, if any.
+    Field {
+        /// The field name.
+        name: String,
+        /// The sub-selections made on this field, if it's a composite type.
+        selections: Option<Vec<Selection>>,
+    },
+    /// A type-conditioned selection set.
+    #[serde(rename_all = "camelCase")]
+    InlineFragment {
+        /// The type this fragment applies to.
+        type_condition: String,
+        /// The selections made within this fragment.
+        selections: Vec<Selection>,
+    },
+}
+
+/// The result of planning a query: the structured [`QueryPlan`], together
+/// with the [`UsageReporting`] metadata the federation query planner
+/// computed for the same operation in the same round-trip into JS.
+#[derive(Debug, PartialEq)]
+pub struct PlanningResponse {
+    /// The query plan.
+    pub query_plan: QueryPlan,
+    /// Usage-reporting metadata for the planned operation.
+    pub usage_reporting: UsageReporting,
+}
+
+/// Operation-reporting metadata computed alongside a [`QueryPlan`], for
+/// usage-based caching keys and metrics reporting.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageReporting {
+    /// The normalized/signature form of the incoming operation, stable
+    /// across variable values and insignificant whitespace.
+    pub stats_report_key: String,
+    /// Per GraphQL type, which fields the operation touched.
+    pub referenced_fields_by_type: HashMap<String, ReferencedFieldsForType>,
+}
+
+/// The fields an operation referenced on a single GraphQL type, as part of
+/// [`UsageReporting`].
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReferencedFieldsForType {
+    /// The names of the referenced fields.
+    pub field_names: Vec<String>,
+    /// Whether this type is a GraphQL interface.
+    pub is_interface: bool,
+}
+
+/// A reusable, stateful query planner.
+///
+/// Constructing a [`Planner`] pays the cost of starting a [`JsRuntime`],
+/// loading `runtime.js` and the bundled `bridge.js`, and pushing the
+/// supplied schema SDL into the runtime exactly once. Each subsequent call to
+/// [`Planner::plan`] only pushes the per-query context and the options into
+/// the already-warm runtime and reads the result back out, which matters when
+/// planning many operations against the same schema. The schema SDL itself is
+/// parsed again by `do_plan.js` on every call; the parsed schema is not kept
+/// between calls.
+///
+/// Because a [`JsRuntime`] is neither [`Send`] nor [`Sync`], a [`Planner`]
+/// is not either, and must be owned and driven from a single thread. A
+/// gateway that wants to plan from multiple threads should instead own a
+/// worker thread that holds the [`Planner`] and communicates with callers
+/// over its own channel, handing back each plan as it's reported into
+/// [`Planner`]'s internal result buffer.
+pub struct Planner {
+    runtime: JsRuntime,
+    options: QueryPlanOptions,
+    result_buffer: Rc<RefCell<Option<serde_json::Value>>>,
+}
+
+/// The shape `do_plan.js` reports back on success: the formatted query plan
+/// as raw JSON, alongside the usage-reporting metadata computed for the
+/// same operation.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BridgePlanningResult {
+    formatted_query_plan: String,
+    usage_reporting: UsageReporting,
+}
+
+/// The error code surfaced when `operation` fails to select exactly one
+/// operation from `query`, per the disambiguation rules documented on
+/// [`OperationalContext::operation`].
+const OPERATION_RESOLUTION_FAILURE: &str = "OPERATION_RESOLUTION_FAILURE";
+
+/// Scan `query` for the names of its top-level operations, without pulling
+/// in a full GraphQL parser. Each entry is `Some(name)` for a named
+/// operation, or `None` for the anonymous/shorthand operation.
+///
+/// This only needs to be accurate enough to resolve `operation` before
+/// handing `query` to graphql-js, which still does the real parsing (and
+/// reports a syntax error for anything this scanner gets wrong).
+fn scan_operation_names(query: &str) -> Vec<Option<String>> {
+    #[derive(PartialEq)]
+    enum Token<'a> {
+        Name(&'a str),
+        Punctuator(char),
+    }
+
+    fn tokens(query: &str) -> impl Iterator<Item = Token<'_>> {
+        let mut rest = query;
+        std::iter::from_fn(move || loop {
+            rest = rest.trim_start();
+            let c = match rest.chars().next() {
+                Some(c) => c,
+                None => return None,
+            };
+
+            if c == '#' {
+                let end = rest.find('\n').unwrap_or(rest.len());
+                rest = &rest[end..];
+                continue;
+            }
+
+            if c == '"' {
+                // Block strings (`"""..."""`) and single-line strings
+                // (`"..."`) are both delimited by however many quotes they
+                // opened with; skip past the matching close so an escaped
+                // `{`/`}`/keyword inside a string literal isn't mistaken
+                // for GraphQL syntax.
+                let delimiter = if rest.starts_with(r#"""""#) { r#"""""# } else { "\"" };
+                let after_open = &rest[delimiter.len()..];
+                let end = after_open
+                    .find(delimiter)
+                    .map(|i| delimiter.len() + i + delimiter.len())
+                    .unwrap_or(rest.len());
+                rest = &rest[end..];
+                continue;
+            }
+
+            if c.is_alphabetic() || c == '_' {
+                let end = rest
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                let (name, remainder) = rest.split_at(end);
+                rest = remainder;
+                return Some(Token::Name(name));
+            }
+
+            rest = &rest[c.len_utf8()..];
+            return Some(Token::Punctuator(c));
+        })
+    }
+
+    let mut names = Vec::new();
+    let mut depth: i32 = 0;
+    // Parens don't nest selection sets (they hold variable definitions,
+    // directive arguments, and field arguments), but they can contain brace
+    // `{ ... }` input-object literals -- e.g. a variable default value like
+    // `($f: F = { a: true })`. While inside parens, braces belong to that
+    // literal, not to a selection set, so `depth` is left alone until the
+    // matching `)`.
+    let mut paren_depth: i32 = 0;
+    // Set once a `query`/`mutation`/`subscription`/`fragment` keyword (with
+    // or without a following name) has been seen at the top level, so the
+    // `{` that opens its body isn't also counted as a separate anonymous
+    // operation.
+    let mut awaiting_operation_body = false;
+    let mut iter = tokens(query).peekable();
+
+    while let Some(token) = iter.next() {
+        match token {
+            Token::Punctuator('(') => paren_depth += 1,
+            Token::Punctuator(')') => paren_depth -= 1,
+            Token::Punctuator('{') if paren_depth == 0 => {
+                if depth == 0 && !awaiting_operation_body {
+                    // Anonymous shorthand operation: `{ ... }` with no
+                    // leading `query`/`mutation`/`subscription` keyword.
+                    names.push(None);
+                }
+                awaiting_operation_body = false;
+                depth += 1;
+            }
+            Token::Punctuator('}') if paren_depth == 0 => depth -= 1,
+            Token::Name(keyword @ ("query" | "mutation" | "subscription")) if depth == 0 => {
+                let _ = keyword;
+                match iter.peek() {
+                    Some(Token::Name(name)) => {
+                        names.push(Some((*name).to_string()));
+                        iter.next();
+                    }
+                    _ => names.push(None),
+                }
+                awaiting_operation_body = true;
+            }
+            // `fragment Name on Type { ... }` isn't an operation, but its
+            // body still needs to be skipped so the `{` that opens it isn't
+            // mistaken for an anonymous operation.
+            Token::Name("fragment") if depth == 0 => {
+                awaiting_operation_body = true;
+            }
+            _ => {}
+        }
+    }
+
+    names
+}
+
+/// Resolve `operation` against the operations present in `query`, before
+/// ever calling in to JS, so a missing or unknown name in a query with
+/// several operations is reported without paying for a JS round-trip. A
+/// name that matches more than one operation, or any name in a query with at
+/// most one operation, is passed through to graphql-js. Returns `None` when
+/// resolution doesn't need to reject anything up front -- including when
+/// `query` fails to parse at all, which is left to graphql-js to diagnose.
+fn resolve_operation(query: &str, operation: &str) -> Option<PlanningError> {
+    let operations = scan_operation_names(query);
+
+    if operations.len() <= 1 {
+        return None;
+    }
+
+    let message = if operation.is_empty() {
+        "Must provide operation name if query contains multiple operations.".to_string()
+    } else if operations
+        .iter()
+        .any(|name| name.as_deref() == Some(operation))
+    {
+        return None;
+    } else {
+        format!("Unknown operation named \"{}\".", operation)
+    };
+
+    Some(PlanningError {
+        message: Some(message),
+        extensions: Some(PlanningErrorExtensions {
+            code: OPERATION_RESOLUTION_FAILURE.to_string(),
+        }),
+    })
+}
+
+/// Recognize the messages graphql-js emits when `operation` failed to
+/// select exactly one operation from the query, and rewrite those errors to
+/// carry the dedicated [`OPERATION_RESOLUTION_FAILURE`] code, rather than
+/// relying on whatever graphql-js happens to label them as.
+///
+/// [`resolve_operation`] catches the missing and unknown name cases of a
+/// multi-operation query before `query` ever reaches JS, so this covers the
+/// cases it passes through (a duplicated operation name, or an unknown name
+/// in a single-operation query) and graphql-js rejecting an operation for a
+/// reason [`scan_operation_names`] didn't anticipate. The messages matched
+/// here were last checked against graphql-js's [`getOperationAST`].
 ///
-pub fn plan(context: OperationalContext, options: QueryPlanOptions) -> Result<String, Vec<PlanningError>> {
-    // Initialize a runtime instance
-    let mut runtime = JsRuntime::new(Default::default());
+/// [`getOperationAST`]: https://github.com/graphql/graphql-js/blob/3869211/src/utilities/getOperationAST.js#L15-L43
+fn normalize_operation_resolution_errors(errors: Vec<PlanningError>) -> Vec<PlanningError> {
+    errors
+        .into_iter()
+        .map(|error| match &error.message {
+            Some(message)
+                if message.starts_with("Must provide operation name")
+                    || message.starts_with("Unknown operation named")
+                    || message == "Must provide an operation."
+                    || message.starts_with("There can be only one operation named") =>
+            {
+                PlanningError {
+                    extensions: Some(PlanningErrorExtensions {
+                        code: OPERATION_RESOLUTION_FAILURE.to_string(),
+                    }),
+                    ..error
+                }
+            }
+            _ => error,
+        })
+        .collect()
+}
+
+/// Parse the raw JSON a planning call produced into a [`PlanningResponse`],
+/// keeping the raw plan JSON around for [`QueryPlan::raw`].
+fn parse_planning_response(result: BridgePlanningResult) -> Result<PlanningResponse, HarmonizerError> {
+    let mut query_plan: QueryPlan = serde_json::from_str(&result.formatted_query_plan)
+        .map_err(|e| HarmonizerError::ResultDeserialization(e.to_string()))?;
+    query_plan.raw = result.formatted_query_plan;
+
+    Ok(PlanningResponse {
+        query_plan,
+        usage_reporting: result.usage_reporting,
+    })
+}
+
+impl Planner {
+    /// Create a [`Planner`] for `schema`, initializing the JS runtime and
+    /// pushing the schema into it once. `options` are applied to every
+    /// subsequent call to [`Planner::plan`].
+    pub fn new(schema: String, options: QueryPlanOptions) -> Result<Planner, HarmonizerError> {
+        let mut runtime = JsRuntime::new(Default::default());
+        let result_buffer = Rc::new(RefCell::new(None));
+
+        register_print_op(&mut runtime);
+        register_result_op(&mut runtime, Rc::clone(&result_buffer));
+
+        // The runtime automatically contains a Deno.core object with several
+        // functions for interacting with it.
+        runtime
+            .execute("<init>", include_str!("../js/runtime.js"))
+            .map_err(|e| HarmonizerError::BridgeInitialization(e.to_string()))?;
+
+        // Load the composition library.
+        runtime
+            .execute("bridge.js", include_str!("../dist/bridge.js"))
+            .map_err(|e| HarmonizerError::BridgeInitialization(e.to_string()))?;
+
+        let schema_javascript = format!(
+            "schemaString = {}",
+            serde_json::to_string(&schema)
+                .map_err(|e| HarmonizerError::RuntimeExecution(e.to_string()))?
+        );
+
+        runtime
+            .execute("<set_schema>", &schema_javascript)
+            .map_err(|e| HarmonizerError::RuntimeExecution(e.to_string()))?;
+
+        Ok(Planner {
+            runtime,
+            options,
+            result_buffer,
+        })
+    }
+
+    /// Plan `query`, optionally selecting `operation` when `query` contains
+    /// more than one named operation. See the disambiguation rules
+    /// documented on [`OperationalContext::operation`]. The schema pushed in
+    /// [`Planner::new`] is reused, so only the query's context and the
+    /// options are pushed into the runtime.
+    pub fn plan(&mut self, query: String, operation: String) -> Result<PlanningResponse, HarmonizerError> {
+        if let Some(error) = resolve_operation(&query, &operation) {
+            return Err(HarmonizerError::Planning(vec![error]));
+        }
+
+        let context_javascript = format!(
+            "context = {}",
+            serde_json::to_string(&PlanningContext { query, operation })
+                .map_err(|e| HarmonizerError::RuntimeExecution(e.to_string()))?
+        );
+
+        let options_javascript = format!(
+            "options = {}",
+            serde_json::to_string(&self.options)
+                .map_err(|e| HarmonizerError::RuntimeExecution(e.to_string()))?
+        );
+
+        self.runtime
+            .execute("<set_context>", &context_javascript)
+            .map_err(|e| HarmonizerError::RuntimeExecution(e.to_string()))?;
+
+        self.runtime
+            .execute("<set_options>", &options_javascript)
+            .map_err(|e| HarmonizerError::RuntimeExecution(e.to_string()))?;
 
-    // We'll use this channel to get the results
-    let (tx, rx) = channel();
+        self.runtime
+            .execute("do_plan.js", include_str!("../js/do_plan.js"))
+            .map_err(|e| HarmonizerError::RuntimeExecution(e.to_string()))?;
 
-    // The first thing we do is define an op so we can print data to STDOUT,
-    // because by default the JavaScript console functions are just stubs (they
-    // don't do anything).
+        let value = self.result_buffer.borrow_mut().take().ok_or_else(|| {
+            HarmonizerError::ResultDeserialization("do_plan.js did not report a result".to_string())
+        })?;
 
+        let result: Result<BridgePlanningResult, Vec<PlanningError>> =
+            serde_json::from_value(value)
+                .map_err(|e| HarmonizerError::ResultDeserialization(e.to_string()))?;
+
+        parse_planning_response(result.map_err(normalize_operation_resolution_errors)?)
+    }
+}
+
+/// The portion of [`OperationalContext`] that changes on every call to
+/// [`Planner::plan`]; the schema is instead fixed for the lifetime of the
+/// [`Planner`].
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PlanningContext {
+    query: String,
+    operation: String,
+}
+
+/// Register the op that lets the JS bridge print to STDOUT, because by
+/// default the JavaScript console functions are just stubs (they don't do
+/// anything).
+fn register_print_op(runtime: &mut JsRuntime) {
     // Register the op for outputting bytes to stdout. It can be invoked with
     // Deno.core.dispatch and the id this method returns or
     // Deno.core.dispatchByName and the name provided.
@@ -120,60 +572,35 @@ pub fn plan(context: OperationalContext, options: QueryPlanOptions) -> Result<St
             Ok(()) // No meaningful result
         }),
     );
+}
 
+/// Register the op that the JS bridge uses to report the planning result
+/// back into `result_buffer`, so it can be read once `execute` returns.
+fn register_result_op(
+    runtime: &mut JsRuntime,
+    result_buffer: Rc<RefCell<Option<serde_json::Value>>>,
+) {
     runtime.register_op(
         "op_composition_result",
         op_sync(move |_state, value, _zero_copy| {
-            tx.send(serde_json::from_value(value).expect("deserializing composition result"))
-                .expect("channel must be open");
+            *result_buffer.borrow_mut() = Some(value);
 
             Ok(serde_json::json!(null))
 
             // Don't return anything to JS
         }),
     );
+}
 
-    // The runtime automatically contains a Deno.core object with several
-    // functions for interacting with it.
-    runtime
-        .execute(
-            "<init>",
-            include_str!("../js/runtime.js"),
-        )
-        .expect("unable to initialize bridge runtime environment");
-
-    // Load the composition library.
-    runtime
-        .execute("bridge.js", include_str!("../dist/bridge.js"))
-        .expect("unable to evaluate bridge module");
-
-    // We literally just turn it into a JSON object that we'll execute within
-    // the runtime.
-    let context_javascript = format!(
-        "context = {}",
-        serde_json::to_string(&context)
-            .expect("unable to serialize query plan context into JavaScript runtime")
-    );
-
-    let options_javascript = format!(
-        "options = {}",
-        serde_json::to_string(&options)
-            .expect("unable to serialize query plan options list into JavaScript runtime")
-    );
-
-    runtime
-        .execute("<set_context>", &context_javascript)
-        .expect("unable to evaluate service list in JavaScript runtime");
-
-    runtime
-        .execute("<set_options>", &options_javascript)
-        .expect("unable to evaluate service list in JavaScript runtime");
-
-    runtime
-        .execute("do_plan.js", include_str!("../js/do_plan.js"))
-        .expect("unable to invoke do_plan in JavaScript runtime");
-
-    rx.recv().expect("channel remains open")
+/// Create the query plan by calling in to JS.
+///
+/// This is a convenience wrapper around [`Planner`] for callers that only
+/// need to plan a single query against a schema. Planning several queries
+/// against the same schema should instead construct one [`Planner`] and
+/// call [`Planner::plan`] repeatedly, which avoids re-initializing the JS
+/// runtime and re-sending the schema on every call.
+pub fn plan(context: OperationalContext, options: QueryPlanOptions) -> Result<PlanningResponse, HarmonizerError> {
+    Planner::new(context.schema, options)?.plan(context.query, context.operation)
 }
 
 #[cfg(test)]
@@ -184,21 +611,43 @@ mod tests {
 
     #[test]
     fn it_works() {
+        insta::assert_debug_snapshot!(plan(OperationalContext {
+            schema: SCHEMA.to_string(),
+            query: QUERY.to_string(),
+            operation: "".to_string()
+        },
+        QueryPlanOptions::DEFAULT
+        ).unwrap().query_plan);
+    }
+
+    #[test]
+    fn the_raw_plan_is_recoverable() {
         insta::assert_snapshot!(plan(OperationalContext {
             schema: SCHEMA.to_string(),
             query: QUERY.to_string(),
             operation: "".to_string()
         },
         QueryPlanOptions::DEFAULT
-        ).unwrap());
+        ).unwrap().query_plan.raw());
+    }
+
+    #[test]
+    fn usage_reporting_is_returned_alongside_the_plan() {
+        insta::assert_debug_snapshot!(plan(OperationalContext {
+            schema: SCHEMA.to_string(),
+            query: QUERY.to_string(),
+            operation: "".to_string()
+        },
+        QueryPlanOptions::DEFAULT
+        ).unwrap().usage_reporting);
     }
 
     #[test]
     fn invalid_schema_is_caught() {
-        let result = Err(vec![PlanningError{
+        let result = Err(HarmonizerError::Planning(vec![PlanningError{
             message: Some("Syntax Error: Unexpected Name \"Garbage\".".to_string()),
             extensions: None
-        }]);
+        }]));
         assert_eq!(result, plan(OperationalContext {
             schema: "Garbage".to_string(),
             query: QUERY.to_string(),
@@ -208,12 +657,115 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn resolve_operation_ignores_fragment_definitions() {
+        let query = "fragment UserFields on User { id name } query GetUser { user { ...UserFields } }";
+        assert_eq!(resolve_operation(query, ""), None);
+
+        let query = "query GetUser { user { ...UserFields } } fragment UserFields on User { id name }";
+        assert_eq!(resolve_operation(query, ""), None);
+    }
+
+    #[test]
+    fn resolve_operation_ignores_braces_in_variable_defaults() {
+        let query = "query GetUser($filter: FilterInput = { isActive: true }) { user(filter: $filter) { id } }";
+        assert_eq!(resolve_operation(query, ""), None);
+    }
+
+    #[test]
+    fn duplicate_operation_name_is_normalized() {
+        let errors = vec![PlanningError {
+            message: Some("There can be only one operation named \"GetUser\".".to_string()),
+            extensions: None,
+        }];
+
+        let normalized = normalize_operation_resolution_errors(errors);
+
+        assert_eq!(normalized[0].code(), "OPERATION_RESOLUTION_FAILURE");
+    }
+
+    #[test]
+    fn ambiguous_operation_selection_is_caught() {
+        const MULTI_OPERATION_QUERY: &str = include_str!("testdata/multi_operation_query.graphql");
+
+        let result = plan(
+            OperationalContext {
+                schema: SCHEMA.to_string(),
+                query: MULTI_OPERATION_QUERY.to_string(),
+                operation: "".to_string(),
+            },
+            QueryPlanOptions::DEFAULT,
+        );
+
+        match result {
+            Err(HarmonizerError::Planning(errors)) => {
+                assert_eq!(errors[0].code(), "OPERATION_RESOLUTION_FAILURE");
+            }
+            other => panic!("expected a planning error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unknown_operation_name_is_caught() {
+        const MULTI_OPERATION_QUERY: &str = include_str!("testdata/multi_operation_query.graphql");
+
+        let result = plan(
+            OperationalContext {
+                schema: SCHEMA.to_string(),
+                query: MULTI_OPERATION_QUERY.to_string(),
+                operation: "NotDefined".to_string(),
+            },
+            QueryPlanOptions::DEFAULT,
+        );
+
+        match result {
+            Err(HarmonizerError::Planning(errors)) => {
+                assert_eq!(errors[0].code(), "OPERATION_RESOLUTION_FAILURE");
+            }
+            other => panic!("expected a planning error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn operation_selects_the_named_operation() {
+        const MULTI_OPERATION_QUERY: &str = include_str!("testdata/multi_operation_query.graphql");
+
+        insta::assert_debug_snapshot!(plan(
+            OperationalContext {
+                schema: SCHEMA.to_string(),
+                query: MULTI_OPERATION_QUERY.to_string(),
+                operation: "GetUser".to_string(),
+            },
+            QueryPlanOptions::DEFAULT,
+        )
+        .unwrap()
+        .query_plan);
+    }
+
+    #[test]
+    fn planner_reuses_the_warm_runtime_across_independent_calls() {
+        const MULTI_OPERATION_QUERY: &str = include_str!("testdata/multi_operation_query.graphql");
+
+        let mut planner = Planner::new(SCHEMA.to_string(), QueryPlanOptions::DEFAULT).unwrap();
+
+        let first = planner.plan(QUERY.to_string(), "".to_string()).unwrap();
+        let second = planner
+            .plan(MULTI_OPERATION_QUERY.to_string(), "GetUser".to_string())
+            .unwrap();
+        assert_ne!(first.query_plan.raw(), second.query_plan.raw());
+
+        // Planning the original query again should be unaffected by the
+        // intervening call, proving no state leaked between calls.
+        let third = planner.plan(QUERY.to_string(), "".to_string()).unwrap();
+        assert_eq!(first.query_plan, third.query_plan);
+    }
+
     #[test]
     fn invalid_query_is_caught() {
-        let result = Err(vec![PlanningError{
+        let result = Err(HarmonizerError::Planning(vec![PlanningError{
             message: Some("Syntax Error: Unexpected Name \"Garbage\".".to_string()),
             extensions: None
-        }]);
+        }]));
         assert_eq!(result, plan(OperationalContext {
             schema: SCHEMA.to_string(),
             query: "Garbage".to_string(),